@@ -1,17 +1,111 @@
-use std::fs::{Metadata, File};
-use std::io::{Initializer, IoSlice, IoSliceMut, Read, Seek, SeekFrom, Write, Result};
+use std::fs::{File, Metadata};
+use std::io::{Initializer, IoSlice, IoSliceMut, Read, Result, Seek, SeekFrom, Write};
+use std::sync::Arc;
 
 use positioned_io::{ReadAt, WriteAt};
-use parking_lot::{RwLock, RwLockReadGuard};
-use lazy_static::lazy_static;
 
-lazy_static! {
-    pub static ref DISK_LOCK: RwLock<u32> = RwLock::new(0_u32);
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawHandle, RawHandle};
+
+/// The OS object an advisory lock is taken against: the raw fd on Unix, the
+/// raw `HANDLE` on Windows.
+#[cfg(unix)]
+type LockHandle = RawFd;
+#[cfg(windows)]
+type LockHandle = RawHandle;
+
+/// RAII guard holding an OS advisory lock on the whole extent of a file.
+///
+/// The lock is released when the guard is dropped. The guard keeps an
+/// `Arc<File>` alive alongside the raw handle it unlocks, so the descriptor
+/// cannot be closed (and its number reused for an unrelated file) while the
+/// guard still exists — dropping the originating `LockedFile` first is safe.
+///
+/// Soundness constraint: advisory locks do **not** stack per file description.
+/// Taking a second lock through the same fd (or a `dup` of it, including a
+/// [`clone_handle`](LockedFile::clone_handle) that shares the `Arc<File>`)
+/// converts the existing region lock, and dropping either guard's `F_UNLCK`
+/// then releases it for both. A `FileLockGuard` is therefore only meaningful
+/// one-at-a-time per underlying fd; concurrent guards on shared-fd handles do
+/// not provide independent mutual exclusion. In-process, the
+/// [`ReaderGate`](struct@ReaderGate) — not the OS lock — serializes readers
+/// and writers that share an fd; the OS lock's guarantee is the cross-process
+/// one, where each process holds a distinct open file description.
+#[must_use = "the advisory lock is released as soon as the guard is dropped"]
+pub struct FileLockGuard {
+    handle: LockHandle,
+    // Keeps the fd open for at least as long as the lock is held. Never read.
+    _file: Arc<File>,
+}
+
+impl Drop for FileLockGuard {
+    fn drop(&mut self) {
+        // Best effort: a failing unlock leaves the lock to be cleaned up when
+        // the fd is closed, so there is nothing useful to do with the error.
+        let _ = imp::unlock(self.handle);
+    }
+}
+
+#[cfg(unix)]
+fn lock_handle(file: &File) -> LockHandle {
+    file.as_raw_fd()
+}
+
+#[cfg(windows)]
+fn lock_handle(file: &File) -> LockHandle {
+    file.as_raw_handle()
+}
+
+/// Positioned read that only needs `&File` (`pread`/`seek_read`), so it works
+/// through a shared `Arc<File>`.
+#[cfg(unix)]
+fn pread(file: &File, pos: u64, buf: &mut [u8]) -> Result<usize> {
+    use std::os::unix::fs::FileExt;
+    file.read_at(buf, pos)
+}
+
+/// Positioned write that only needs `&File` (`pwrite`/`seek_write`).
+#[cfg(unix)]
+fn pwrite(file: &File, pos: u64, buf: &[u8]) -> Result<usize> {
+    use std::os::unix::fs::FileExt;
+    file.write_at(buf, pos)
+}
+
+#[cfg(windows)]
+fn pread(file: &File, pos: u64, buf: &mut [u8]) -> Result<usize> {
+    use std::os::windows::fs::FileExt;
+    file.seek_read(buf, pos)
+}
+
+#[cfg(windows)]
+fn pwrite(file: &File, pos: u64, buf: &[u8]) -> Result<usize> {
+    use std::os::windows::fs::FileExt;
+    file.seek_write(buf, pos)
+}
+
+/// Access-pattern hint passed to [`LockedFile::advise`], mapping onto
+/// `posix_fadvise` advice values where supported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Advice {
+    Normal,
+    Sequential,
+    Random,
+    WillNeed,
+    DontNeed,
 }
 
 pub struct LockedFile {
-    pub file: File,
+    // Shared so a `FileLockGuard` or `Reader` can keep the descriptor alive.
+    file: Arc<File>,
     pub no_lock: bool,
+    // Cursor for the sequential `Read`/`Write`/`Seek` surface; positioned I/O
+    // ignores it. Kept here, not in the kernel fd, so it is private to this
+    // handle and survives descriptor sharing.
+    pos: u64,
+    // Coordinates in-process readers with an exclusive writer (see `reader`).
+    gate: Arc<ReaderGate>,
 }
 
 impl LockedFile {
@@ -21,17 +115,76 @@ impl LockedFile {
 
     pub fn new_lock(file: File, no_lock: bool) -> LockedFile {
         LockedFile {
-            file,
+            file: Arc::new(file),
             no_lock,
+            pos: 0,
+            gate: Arc::new(ReaderGate::default()),
         }
     }
-    
-    fn lock<'a, 'b>(&'a self) -> Option<RwLockReadGuard<'b, u32>> {
+
+    /// Take a blocking shared (read) advisory lock for the duration of the
+    /// returned guard. Returns `Ok(None)` when locking is bypassed via
+    /// `no_lock`.
+    fn lock_shared(&self) -> Result<Option<FileLockGuard>> {
+        self.lock(false)
+    }
+
+    /// Take a blocking exclusive (write) advisory lock for the duration of the
+    /// returned guard. Returns `Ok(None)` when locking is bypassed via
+    /// `no_lock`.
+    fn lock_exclusive(&self) -> Result<Option<FileLockGuard>> {
+        self.lock(true)
+    }
+
+    fn lock(&self, exclusive: bool) -> Result<Option<FileLockGuard>> {
         if self.no_lock {
-            None // skip locking
-        } else {
-            // Some(DISK_LOCK.read())
-            None
+            return Ok(None); // skip locking
+        }
+        let handle = lock_handle(&self.file);
+        // A blocking `fcntl` lock can fail for real runtime reasons — notably
+        // `EINTR` (retried inside `imp::lock`) and `EDEADLK` — so the error is
+        // propagated to the caller rather than turned into a panic.
+        imp::lock(handle, exclusive, true)?;
+        Ok(Some(FileLockGuard { handle, _file: self.file.clone() }))
+    }
+
+    /// Try to take a shared (read) advisory lock without blocking.
+    ///
+    /// Returns `Ok(None)` when locking is bypassed via `no_lock`, and an error
+    /// of kind [`ErrorKind::WouldBlock`](std::io::ErrorKind::WouldBlock) when
+    /// the lock is held exclusively by another holder.
+    pub fn try_lock_shared(&self) -> Result<Option<FileLockGuard>> {
+        self.try_lock(false)
+    }
+
+    /// Try to take an exclusive (write) advisory lock without blocking.
+    ///
+    /// Returns `Ok(None)` when locking is bypassed via `no_lock`, and an error
+    /// of kind [`ErrorKind::WouldBlock`](std::io::ErrorKind::WouldBlock) when
+    /// the lock is contended.
+    pub fn try_lock_exclusive(&self) -> Result<Option<FileLockGuard>> {
+        self.try_lock(true)
+    }
+
+    fn try_lock(&self, exclusive: bool) -> Result<Option<FileLockGuard>> {
+        if self.no_lock {
+            return Ok(None);
+        }
+        let handle = lock_handle(&self.file);
+        imp::lock(handle, exclusive, false)?;
+        Ok(Some(FileLockGuard { handle, _file: self.file.clone() }))
+    }
+
+    /// Cheaply clone this handle, sharing the same underlying descriptor and
+    /// reader gate. The clone starts with its own sequential cursor at zero.
+    /// Used by the descriptor pool to hand a resident `LockedFile` back to a
+    /// caller without holding the pool lock across the I/O.
+    pub fn clone_handle(&self) -> LockedFile {
+        LockedFile {
+            file: self.file.clone(),
+            no_lock: self.no_lock,
+            pos: 0,
+            gate: self.gate.clone(),
         }
     }
 
@@ -40,30 +193,103 @@ impl LockedFile {
     }
 
     pub fn sync_all(&self) -> Result<()> {
-        let _guard = self.lock();
+        let _lease = self.gate.write_lease();
+        let _guard = self.lock_exclusive()?;
         self.file.sync_all()
     }
 
     pub fn sync_data(&self) -> Result<()> {
-        let _guard = self.lock();
+        let _lease = self.gate.write_lease();
+        let _guard = self.lock_exclusive()?;
         self.file.sync_data()
     }
 
     pub fn set_len(&self, size: u64) -> Result<()> {
-        let _guard = self.lock();
+        let _lease = self.gate.write_lease();
+        let _guard = self.lock_exclusive()?;
         self.file.set_len(size)
     }
+
+    /// Hint the kernel about the expected access pattern for `[offset, offset +
+    /// len)` via `posix_fadvise`. A `len` of zero covers the rest of the file.
+    /// Degrades to a no-op on platforms without `posix_fadvise`.
+    pub fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        imp::advise(&self.file, offset, len, advice)
+    }
+
+    /// Preallocate `[offset, offset + len)` via `fallocate`, avoiding
+    /// fragmentation when the whole tree file size is known up front. Falls
+    /// back to extending the file with `set_len` where `fallocate` is
+    /// unavailable or unsupported by the filesystem.
+    pub fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+        let _lease = self.gate.write_lease();
+        let _guard = self.lock_exclusive()?;
+        imp::allocate(&self.file, offset, len)
+    }
+
+    /// Read the contiguous range starting at `pos` into several buffers in a
+    /// single `preadv` syscall. Returns the total number of bytes read. Loops over the
+    /// buffers with positioned reads where `preadv` is unavailable.
+    pub fn read_vectored_at(&self, pos: u64, bufs: &mut [IoSliceMut<'_>]) -> Result<usize> {
+        let _guard = self.lock_shared()?;
+        imp::read_vectored_at(&self.file, pos, bufs)
+    }
+
+    /// Scatter-write several buffers starting at `pos` in a single `pwritev`
+    /// syscall. Returns the total number of bytes written. Loops over the
+    /// buffers with positioned writes where `pwritev` is unavailable.
+    pub fn write_vectored_at(&mut self, pos: u64, bufs: &[IoSlice<'_>]) -> Result<usize> {
+        let _lease = self.gate.write_lease();
+        let _guard = self.lock_exclusive()?;
+        imp::write_vectored_at(&self.file, pos, bufs)
+    }
+
+    /// Hand out a lightweight [`Reader`] with its own descriptor and private
+    /// cursor over this store. Readers register with an in-process gate for
+    /// their lifetime; the exclusive write paths above hold a write lease
+    /// across the whole operation. The gate is writer-preferring — once a
+    /// writer is waiting, new readers block — so a write observes a point where
+    /// no reader is resident and runs to completion before any queued reader
+    /// resumes. A reader created while a write is in progress blocks until that
+    /// write finishes, so it never observes a torn write.
+    ///
+    /// The OS advisory lock is a *cross-process* guard only: POSIX `fcntl`
+    /// locks are keyed by `(process, inode)` and do not distinguish threads of
+    /// one process, so they cannot serialize a local reader against a local
+    /// writer — the in-process gate does that. A reader therefore takes no
+    /// long-lived OS lock of its own (one would be released out from under it
+    /// the moment any write path dropped its per-call guard); each positioned
+    /// read still takes a short shared lock for cross-process safety.
+    pub fn reader(&self) -> Result<Reader> {
+        let file = Arc::new(self.file.try_clone()?);
+        Ok(Reader {
+            _gate: ReaderGuard::new(self.gate.clone()),
+            no_lock: self.no_lock,
+            pos: 0,
+            file,
+        })
+    }
+
+    /// Number of live [`Reader`] handles currently open on this store.
+    pub fn reader_count(&self) -> usize {
+        self.gate.count()
+    }
+
+    /// Block until the resident [`Reader`]s have drained, parking on a
+    /// `Condvar` rather than busy-spinning. Implemented as taking and releasing
+    /// the write lease, so the wait is writer-preferring and cannot be starved
+    /// by a steady stream of newly created readers.
+    pub fn wait_for_readers(&self) {
+        self.gate.wait_for_readers();
+    }
 }
 
 impl Read for LockedFile {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let _guard = self.lock();
-        self.file.read(buf)
-    }
-
-    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> Result<usize> {
-        let _guard = self.lock();
-        self.file.read_vectored(bufs)
+        let _guard = self.lock_shared()?;
+        let n = pread(&self.file, self.pos, buf)?;
+        self.pos += n as u64;
+        Ok(n)
     }
 
     #[allow(unsafe_code)]
@@ -75,39 +301,44 @@ impl Read for LockedFile {
 
 impl Write for LockedFile {
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        let _guard = self.lock();
-        self.file.write(buf)
-    }
-
-    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize> {
-        let _guard = self.lock();
-        self.file.write_vectored(bufs)
+        let _lease = self.gate.write_lease();
+        let _guard = self.lock_exclusive()?;
+        let n = pwrite(&self.file, self.pos, buf)?;
+        self.pos += n as u64;
+        Ok(n)
     }
 
     fn flush(&mut self) -> Result<()> {
-        let _guard = self.lock();
-        Write::flush(&mut self.file)
+        // Positioned writes reach the fd directly; there is no userspace buffer
+        // to flush, so this is a no-op matching `File`'s own `flush`.
+        Ok(())
     }
 }
 
 impl Seek for LockedFile {
     fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
-        let _guard = self.lock();
-        self.file.seek(pos)
+        let new = match pos {
+            SeekFrom::Start(n) => n,
+            SeekFrom::End(n) => (self.metadata()?.len() as i64 + n) as u64,
+            SeekFrom::Current(n) => (self.pos as i64 + n) as u64,
+        };
+        self.pos = new;
+        Ok(new)
     }
 }
 
 impl ReadAt for LockedFile {
     fn read_at(&self, pos: u64, buf: &mut [u8]) -> Result<usize> {
-        let _guard = self.lock();
-        self.file.read_at(pos, buf)
+        let _guard = self.lock_shared()?;
+        pread(&self.file, pos, buf)
     }
 }
 
 impl WriteAt for LockedFile {
     fn write_at(&mut self, pos: u64, buf: &[u8]) -> Result<usize> {
-        let _guard = self.lock();
-        self.file.write_at(pos, buf)
+        let _lease = self.gate.write_lease();
+        let _guard = self.lock_exclusive()?;
+        pwrite(&self.file, pos, buf)
     }
     fn flush(&mut self) -> Result<()> {
         Write::flush(self)
@@ -119,3 +350,423 @@ impl std::fmt::Debug for LockedFile {
         self.file.fmt(f)
     }
 }
+
+/// In-process coordination between concurrent [`Reader`]s and an exclusive
+/// writer. POSIX advisory locks are per-process, so they cannot serialize
+/// threads of one process; this gate does, parking waiters on a `Condvar`
+/// rather than busy-spinning so neither side pins a core.
+///
+/// It is a writer-preferring readers–writer gate: a write lease waits for the
+/// resident readers to drain, and while a writer is active *or queued* new
+/// readers block. That ordering closes two holes a plain reader counter has —
+/// a reader created during the write window cannot start an overlapping read
+/// (no torn read), and a steady stream of new readers can no longer starve a
+/// waiting writer indefinitely.
+#[derive(Default)]
+struct ReaderGate {
+    state: std::sync::Mutex<GateState>,
+    cv: std::sync::Condvar,
+}
+
+#[derive(Default)]
+struct GateState {
+    readers: usize,
+    writers_waiting: usize,
+    writing: bool,
+}
+
+impl ReaderGate {
+    /// Register a reader, blocking while a writer holds or is waiting for the
+    /// lease so the reader cannot overlap a write.
+    fn acquire(&self) {
+        let mut s = self.state.lock().unwrap();
+        while s.writing || s.writers_waiting > 0 {
+            s = self.cv.wait(s).unwrap();
+        }
+        s.readers += 1;
+    }
+
+    fn release(&self) {
+        let mut s = self.state.lock().unwrap();
+        s.readers -= 1;
+        if s.readers == 0 {
+            self.cv.notify_all();
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.state.lock().unwrap().readers
+    }
+
+    /// Acquire the exclusive write lease, returned as an RAII guard. New
+    /// readers are held off from the moment this is called (writer preference);
+    /// the lease is released when the returned guard is dropped.
+    fn write_lease(&self) -> WriteLease<'_> {
+        let mut s = self.state.lock().unwrap();
+        s.writers_waiting += 1;
+        while s.writing || s.readers > 0 {
+            s = self.cv.wait(s).unwrap();
+        }
+        s.writers_waiting -= 1;
+        s.writing = true;
+        WriteLease { gate: self }
+    }
+
+    /// Barrier helper: take and immediately drop a write lease, so on return
+    /// every reader that was resident has drained and no writer is active.
+    fn wait_for_readers(&self) {
+        drop(self.write_lease());
+    }
+}
+
+/// Held for the duration of one write; blocks new readers and other writers
+/// until dropped.
+struct WriteLease<'a> {
+    gate: &'a ReaderGate,
+}
+
+impl Drop for WriteLease<'_> {
+    fn drop(&mut self) {
+        let mut s = self.gate.state.lock().unwrap();
+        s.writing = false;
+        self.gate.cv.notify_all();
+    }
+}
+
+/// Registers a reader with the gate on creation and deregisters it on drop.
+struct ReaderGuard {
+    gate: Arc<ReaderGate>,
+}
+
+impl ReaderGuard {
+    fn new(gate: Arc<ReaderGate>) -> ReaderGuard {
+        gate.acquire();
+        ReaderGuard { gate }
+    }
+}
+
+impl Drop for ReaderGuard {
+    fn drop(&mut self) {
+        self.gate.release();
+    }
+}
+
+/// An independent view onto a [`LockedFile`]'s store, with its own descriptor
+/// and private cursor. Cheap to create; registered with the store's reader
+/// gate for its lifetime so an exclusive writer waits until it is dropped.
+pub struct Reader {
+    // Declared before `file` so the gate is released before the fd is dropped.
+    _gate: ReaderGuard,
+    no_lock: bool,
+    pos: u64,
+    file: Arc<File>,
+}
+
+impl Reader {
+    /// Take a short-lived shared lock for cross-process safety around one
+    /// positioned read. In-process exclusion is handled by the reader gate.
+    fn lock_shared(&self) -> Result<Option<FileLockGuard>> {
+        if self.no_lock {
+            return Ok(None);
+        }
+        let handle = lock_handle(&self.file);
+        imp::lock(handle, false, true)?;
+        Ok(Some(FileLockGuard { handle, _file: self.file.clone() }))
+    }
+}
+
+impl ReadAt for Reader {
+    fn read_at(&self, pos: u64, buf: &mut [u8]) -> Result<usize> {
+        let _guard = self.lock_shared()?;
+        pread(&self.file, pos, buf)
+    }
+}
+
+impl Read for Reader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let _guard = self.lock_shared()?;
+        let n = pread(&self.file, self.pos, buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for Reader {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let new = match pos {
+            SeekFrom::Start(n) => n,
+            SeekFrom::End(n) => (self.file.metadata()?.len() as i64 + n) as u64,
+            SeekFrom::Current(n) => (self.pos as i64 + n) as u64,
+        };
+        self.pos = new;
+        Ok(new)
+    }
+}
+
+impl std::fmt::Debug for Reader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.file.fmt(f)
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::File;
+    use std::io::{Error, ErrorKind, IoSlice, IoSliceMut, Result};
+    use std::os::unix::io::{AsRawFd, RawFd};
+
+    use super::Advice;
+
+    /// Prefer open-file-description locks where the platform provides them:
+    /// unlike classic `fcntl` locks they are owned by the open file
+    /// description, so they are per-`fd` and survive being released by an
+    /// unrelated `fd` on the same inode.
+    #[cfg(target_os = "linux")]
+    fn lock_cmd(block: bool) -> libc::c_int {
+        if block {
+            libc::F_OFD_SETLKW
+        } else {
+            libc::F_OFD_SETLK
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn lock_cmd(block: bool) -> libc::c_int {
+        if block {
+            libc::F_SETLKW
+        } else {
+            libc::F_SETLK
+        }
+    }
+
+    fn flock(fd: RawFd, l_type: libc::c_short, block: bool) -> Result<()> {
+        let mut fl: libc::flock = unsafe { std::mem::zeroed() };
+        fl.l_type = l_type;
+        fl.l_whence = libc::SEEK_SET as libc::c_short;
+        fl.l_start = 0;
+        fl.l_len = 0; // zero length means "to end of file", i.e. the whole extent
+
+        let cmd = lock_cmd(block);
+        loop {
+            let ret = unsafe { libc::fcntl(fd, cmd, &fl) };
+            if ret != -1 {
+                return Ok(());
+            }
+            let err = Error::last_os_error();
+            // A blocking lock interrupted by a signal is retried rather than
+            // surfaced as an error or a panic.
+            if err.kind() == ErrorKind::Interrupted {
+                continue;
+            }
+            // A contended non-blocking lock reports EACCES or EAGAIN; surface it
+            // as `WouldBlock` so `try_lock_*` callers can branch on it.
+            if !block {
+                if let Some(code) = err.raw_os_error() {
+                    if code == libc::EACCES || code == libc::EAGAIN {
+                        return Err(Error::new(ErrorKind::WouldBlock, err));
+                    }
+                }
+            }
+            return Err(err);
+        }
+    }
+
+    pub(super) fn lock(fd: RawFd, exclusive: bool, block: bool) -> Result<()> {
+        let l_type = if exclusive { libc::F_WRLCK } else { libc::F_RDLCK };
+        flock(fd, l_type as libc::c_short, block)
+    }
+
+    pub(super) fn unlock(fd: RawFd) -> Result<()> {
+        flock(fd, libc::F_UNLCK as libc::c_short, true)
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(super) fn advise(file: &File, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        let adv = match advice {
+            Advice::Normal => libc::POSIX_FADV_NORMAL,
+            Advice::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+            Advice::Random => libc::POSIX_FADV_RANDOM,
+            Advice::WillNeed => libc::POSIX_FADV_WILLNEED,
+            Advice::DontNeed => libc::POSIX_FADV_DONTNEED,
+        };
+        // posix_fadvise returns the errno directly rather than via `errno`.
+        let ret = unsafe {
+            libc::posix_fadvise(file.as_raw_fd(), offset as libc::off_t, len as libc::off_t, adv)
+        };
+        if ret != 0 {
+            return Err(Error::from_raw_os_error(ret));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(super) fn advise(_file: &File, _offset: u64, _len: u64, _advice: Advice) -> Result<()> {
+        Ok(())
+    }
+
+    pub(super) fn allocate(file: &File, offset: u64, len: u64) -> Result<()> {
+        #[cfg(target_os = "linux")]
+        {
+            let ret = unsafe {
+                libc::fallocate(file.as_raw_fd(), 0, offset as libc::off_t, len as libc::off_t)
+            };
+            if ret == 0 {
+                return Ok(());
+            }
+            // Unsupported filesystems report EOPNOTSUPP/ENOSYS; fall through to
+            // the portable `set_len` extension below. Any other error is real.
+            let err = Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EOPNOTSUPP) | Some(libc::ENOSYS) => {}
+                _ => return Err(err),
+            }
+        }
+        let needed = offset.saturating_add(len);
+        if file.metadata()?.len() < needed {
+            file.set_len(needed)?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn read_vectored_at(
+        file: &File,
+        pos: u64,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Result<usize> {
+        // `IoSliceMut` is guaranteed to be ABI-compatible with `struct iovec`.
+        let ret = unsafe {
+            libc::preadv(
+                file.as_raw_fd(),
+                bufs.as_mut_ptr() as *const libc::iovec,
+                bufs.len() as libc::c_int,
+                pos as libc::off_t,
+            )
+        };
+        if ret < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(ret as usize)
+    }
+
+    pub(super) fn write_vectored_at(file: &File, pos: u64, bufs: &[IoSlice<'_>]) -> Result<usize> {
+        let ret = unsafe {
+            libc::pwritev(
+                file.as_raw_fd(),
+                bufs.as_ptr() as *const libc::iovec,
+                bufs.len() as libc::c_int,
+                pos as libc::off_t,
+            )
+        };
+        if ret < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(ret as usize)
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::fs::File;
+    use std::io::{Error, ErrorKind, IoSlice, IoSliceMut, Result};
+    use std::os::windows::fs::FileExt;
+    use std::os::windows::io::RawHandle;
+
+    use winapi::shared::minwindef::DWORD;
+    use winapi::um::fileapi::{LockFileEx, UnlockFileEx};
+    use winapi::um::minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY, OVERLAPPED};
+
+    use super::Advice;
+
+    // Lock the full addressable extent of the file.
+    const LEN_LOW: DWORD = DWORD::max_value();
+    const LEN_HIGH: DWORD = DWORD::max_value();
+
+    pub(super) fn lock(handle: RawHandle, exclusive: bool, block: bool) -> Result<()> {
+        let mut flags: DWORD = 0;
+        if exclusive {
+            flags |= LOCKFILE_EXCLUSIVE_LOCK;
+        }
+        if !block {
+            flags |= LOCKFILE_FAIL_IMMEDIATELY;
+        }
+        let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+        let ret = unsafe {
+            LockFileEx(
+                handle as _,
+                flags,
+                0,
+                LEN_LOW,
+                LEN_HIGH,
+                &mut overlapped,
+            )
+        };
+        if ret == 0 {
+            let err = Error::last_os_error();
+            if !block {
+                // ERROR_LOCK_VIOLATION == 33
+                if err.raw_os_error() == Some(33) {
+                    return Err(Error::new(ErrorKind::WouldBlock, err));
+                }
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    pub(super) fn unlock(handle: RawHandle) -> Result<()> {
+        let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+        let ret = unsafe {
+            UnlockFileEx(handle as _, 0, LEN_LOW, LEN_HIGH, &mut overlapped)
+        };
+        if ret == 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub(super) fn advise(_file: &File, _offset: u64, _len: u64, _advice: Advice) -> Result<()> {
+        // No direct `posix_fadvise` equivalent; readahead is managed by the
+        // flags passed to `CreateFile`, so this is a no-op.
+        Ok(())
+    }
+
+    pub(super) fn allocate(file: &File, offset: u64, len: u64) -> Result<()> {
+        let needed = offset.saturating_add(len);
+        if file.metadata()?.len() < needed {
+            file.set_len(needed)?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn read_vectored_at(
+        file: &File,
+        pos: u64,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Result<usize> {
+        let mut total = 0;
+        let mut off = pos;
+        for buf in bufs {
+            let n = file.seek_read(buf, off)?;
+            total += n;
+            off += n as u64;
+            if n < buf.len() {
+                break;
+            }
+        }
+        Ok(total)
+    }
+
+    pub(super) fn write_vectored_at(file: &File, pos: u64, bufs: &[IoSlice<'_>]) -> Result<usize> {
+        let mut total = 0;
+        let mut off = pos;
+        for buf in bufs {
+            let n = file.seek_write(buf, off)?;
+            total += n;
+            off += n as u64;
+            if n < buf.len() {
+                break;
+            }
+        }
+        Ok(total)
+    }
+}
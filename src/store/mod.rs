@@ -0,0 +1,7 @@
+pub mod disk_lock;
+pub mod env;
+pub mod virtual_file;
+
+pub use disk_lock::{Advice, FileLockGuard, LockedFile, Reader};
+pub use env::{DiskEnv, Env, MemEnv, StoreReader, StoreWriter};
+pub use virtual_file::VirtualFile;
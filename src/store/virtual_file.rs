@@ -0,0 +1,333 @@
+use std::fs::OpenOptions;
+use std::io::{Read, Result, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use positioned_io::{ReadAt, WriteAt};
+use parking_lot::Mutex;
+use lazy_static::lazy_static;
+
+use super::disk_lock::LockedFile;
+
+/// Default number of live descriptors the pool keeps open at once.
+pub const DEFAULT_POOL_SIZE: usize = 256;
+
+lazy_static! {
+    static ref POOL: Mutex<Pool> = Mutex::new(Pool::new(DEFAULT_POOL_SIZE));
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A `LockedFile` bound to a path rather than to a live descriptor.
+///
+/// The backing `File` is opened lazily on first access and registered in a
+/// fixed-size global pool. When the pool is full the least-recently-used
+/// descriptor is closed (clock / second-chance eviction); a later access to an
+/// evicted `VirtualFile` transparently reopens it with the stored
+/// `OpenOptions`. Per-file position is tracked here, outside the cached
+/// descriptor, so reopening is always correct.
+///
+/// Because the same options drive both the first open and every reopen,
+/// `truncate` and `create_new` are stripped from them at construction (see
+/// [`new_lock`](VirtualFile::new_lock)): either would discard the live store
+/// the first time an evicted descriptor is reopened.
+pub struct VirtualFile {
+    id: u64,
+    path: PathBuf,
+    options: OpenOptions,
+    no_lock: bool,
+    pos: AtomicU64,
+}
+
+impl VirtualFile {
+    /// Bind a `VirtualFile` to `path`, to be opened with `options` on demand.
+    pub fn new<P: AsRef<Path>>(path: P, options: OpenOptions) -> VirtualFile {
+        VirtualFile::new_lock(path, options, false)
+    }
+
+    pub fn new_lock<P: AsRef<Path>>(path: P, options: OpenOptions, no_lock: bool) -> VirtualFile {
+        // The stored options are replayed on every reopen-after-eviction, so a
+        // truncating or exclusive-create open would wipe (or fail on) the live
+        // store the first time its descriptor is recycled. Force both off; the
+        // file is only ever created on the very first open via `create`.
+        let mut options = options;
+        options.truncate(false).create_new(false);
+        VirtualFile {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            path: path.as_ref().to_path_buf(),
+            options,
+            no_lock,
+            pos: AtomicU64::new(0),
+        }
+    }
+
+    /// Resize the global descriptor pool. Evicts descriptors beyond the new
+    /// capacity; a value of zero is clamped to one.
+    pub fn set_pool_size(size: usize) {
+        POOL.lock().resize(size.max(1));
+    }
+
+    /// Current global descriptor pool capacity.
+    pub fn pool_size() -> usize {
+        POOL.lock().capacity()
+    }
+
+    /// Check out the resident descriptor for this file, reopening and
+    /// installing it (evicting if necessary) when it is not cached. The pool
+    /// lock is held only for the check-out; the returned handle shares the fd,
+    /// so the actual I/O runs without the pool lock and many `VirtualFile`s can read and
+    /// write concurrently.
+    fn checkout(&self) -> Result<LockedFile> {
+        let mut pool = POOL.lock();
+        let file = pool.get(self.id, &self.path, &self.options, self.no_lock)?;
+        Ok(file.clone_handle())
+    }
+
+    pub fn metadata(&self) -> Result<std::fs::Metadata> {
+        self.checkout()?.metadata()
+    }
+
+    pub fn sync_all(&self) -> Result<()> {
+        self.checkout()?.sync_all()
+    }
+
+    pub fn sync_data(&self) -> Result<()> {
+        self.checkout()?.sync_data()
+    }
+
+    pub fn set_len(&self, size: u64) -> Result<()> {
+        self.checkout()?.set_len(size)
+    }
+}
+
+impl ReadAt for VirtualFile {
+    fn read_at(&self, pos: u64, buf: &mut [u8]) -> Result<usize> {
+        self.checkout()?.read_at(pos, buf)
+    }
+}
+
+impl WriteAt for VirtualFile {
+    fn write_at(&mut self, pos: u64, buf: &[u8]) -> Result<usize> {
+        WriteAt::write_at(&mut self.checkout()?, pos, buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.checkout()?.sync_data()
+    }
+}
+
+impl Read for VirtualFile {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let pos = self.pos.load(Ordering::Relaxed);
+        let n = self.read_at(pos, buf)?;
+        self.pos.store(pos + n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+impl Write for VirtualFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let pos = self.pos.load(Ordering::Relaxed);
+        let n = self.write_at(pos, buf)?;
+        self.pos.store(pos + n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        WriteAt::flush(self)
+    }
+}
+
+impl Seek for VirtualFile {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let new = match pos {
+            SeekFrom::Start(n) => n,
+            SeekFrom::End(n) => {
+                let len = self.metadata()?.len();
+                (len as i64 + n) as u64
+            }
+            SeekFrom::Current(n) => (self.pos.load(Ordering::Relaxed) as i64 + n) as u64,
+        };
+        self.pos.store(new, Ordering::Relaxed);
+        Ok(new)
+    }
+}
+
+impl std::fmt::Debug for VirtualFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VirtualFile")
+            .field("path", &self.path)
+            .field("no_lock", &self.no_lock)
+            .finish()
+    }
+}
+
+/// One resident descriptor plus its clock bit.
+struct Slot {
+    id: u64,
+    file: LockedFile,
+    recently_used: bool,
+}
+
+/// Fixed-size pool of open descriptors evicted via the clock algorithm.
+struct Pool {
+    slots: Vec<Option<Slot>>,
+    hand: usize,
+}
+
+impl Pool {
+    fn new(capacity: usize) -> Pool {
+        let mut slots = Vec::with_capacity(capacity);
+        slots.resize_with(capacity, || None);
+        Pool { slots, hand: 0 }
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn resize(&mut self, capacity: usize) {
+        if capacity >= self.slots.len() {
+            self.slots.resize_with(capacity, || None);
+        } else {
+            // Drop the tail slots (closing those descriptors) and keep the head.
+            self.slots.truncate(capacity);
+            if self.hand >= capacity {
+                self.hand = 0;
+            }
+        }
+    }
+
+    /// Return the resident descriptor for `id`, opening and installing it
+    /// (evicting if necessary) when it is not currently cached.
+    fn get(
+        &mut self,
+        id: u64,
+        path: &Path,
+        options: &OpenOptions,
+        no_lock: bool,
+    ) -> Result<&mut LockedFile> {
+        let idx = match self.find(id) {
+            Some(idx) => idx,
+            None => {
+                let file = LockedFile::new_lock(options.open(path)?, no_lock);
+                let idx = self.evict();
+                self.slots[idx] = Some(Slot {
+                    id,
+                    file,
+                    recently_used: true,
+                });
+                idx
+            }
+        };
+        let slot = self.slots[idx].as_mut().unwrap();
+        slot.recently_used = true;
+        Ok(&mut slot.file)
+    }
+
+    fn find(&self, id: u64) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|s| s.as_ref().map_or(false, |slot| slot.id == id))
+    }
+
+    /// Pick a slot to (re)use via second-chance: sweep the clock hand, giving
+    /// each recently-used slot one reprieve (its bit cleared) before the first
+    /// slot found already clear is evicted and returned.
+    fn evict(&mut self) -> usize {
+        let len = self.slots.len();
+        loop {
+            let idx = self.hand;
+            self.hand = (self.hand + 1) % len;
+            match &mut self.slots[idx] {
+                None => return idx,
+                Some(slot) if slot.recently_used => slot.recently_used = false,
+                Some(_) => {
+                    self.slots[idx] = None; // closes the evicted descriptor
+                    return idx;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+
+    fn temp_path(tag: &str) -> PathBuf {
+        static N: AtomicU64 = AtomicU64::new(0);
+        let mut p = std::env::temp_dir();
+        p.push(format!(
+            "merkle_vf_{}_{}_{}",
+            std::process::id(),
+            N.fetch_add(1, AtomicOrdering::Relaxed),
+            tag,
+        ));
+        p
+    }
+
+    fn rw() -> OpenOptions {
+        let mut o = OpenOptions::new();
+        o.read(true).write(true).create(true);
+        o
+    }
+
+    #[test]
+    fn clock_sweeps_the_hand_across_resident_slots() {
+        let (a, b, c, d) = (
+            temp_path("a"),
+            temp_path("b"),
+            temp_path("c"),
+            temp_path("d"),
+        );
+        let mut pool = Pool::new(2);
+        pool.get(0, &a, &rw(), false).unwrap();
+        pool.get(1, &b, &rw(), false).unwrap();
+
+        // Both reference bits are set, so inserting a third entry sweeps the
+        // hand (clearing both) and reuses the slot it returns to first.
+        pool.get(2, &c, &rw(), false).unwrap();
+        assert!(pool.find(0).is_none());
+        assert!(pool.find(1).is_some());
+        assert!(pool.find(2).is_some());
+
+        // The hand has advanced, so the next insert evicts the other survivor.
+        pool.get(3, &d, &rw(), false).unwrap();
+        assert!(pool.find(1).is_none());
+        assert!(pool.find(2).is_some());
+        assert!(pool.find(3).is_some());
+
+        for p in [&a, &b, &c, &d] {
+            let _ = std::fs::remove_file(p);
+        }
+    }
+
+    #[test]
+    fn reopen_after_eviction_reads_written_data() {
+        let path = temp_path("reopen");
+        let other = temp_path("other");
+        let mut pool = Pool::new(1);
+
+        {
+            let f = pool.get(0, &path, &rw(), false).unwrap();
+            f.write_at(3, b"hello").unwrap();
+            f.sync_data().unwrap();
+        }
+
+        // Checking out a different file evicts id 0, closing its descriptor.
+        pool.get(1, &other, &rw(), false).unwrap();
+        assert!(pool.find(0).is_none());
+
+        // Re-accessing id 0 reopens the path; data at the original offset is
+        // intact, proving positioned reads survive reopen-after-evict.
+        let f = pool.get(0, &path, &rw(), false).unwrap();
+        let mut buf = [0u8; 5];
+        assert_eq!(f.read_at(3, &mut buf).unwrap(), 5);
+        assert_eq!(&buf, b"hello");
+
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&other);
+    }
+}